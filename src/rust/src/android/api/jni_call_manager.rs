@@ -10,7 +10,7 @@
 
 use jni::objects::{JClass, JObject, JString};
 use jni::strings::JavaStr;
-use jni::sys::{jboolean, jbyteArray, jint, jlong, jobject};
+use jni::sys::{jboolean, jbyteArray, jfloatArray, jint, jlong, jlongArray, jobject};
 use jni::JNIEnv;
 
 use crate::android::android_platform::AndroidPlatform;
@@ -145,6 +145,7 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcProceed(
     jni_call_context: JObject,
     data_mode: jint,
     audio_levels_interval_millis: jint,
+    stats_interval_millis: jint,
 ) {
     let audio_levels_interval = if audio_levels_interval_millis <= 0 {
         None
@@ -152,18 +153,24 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcProceed(
         Some(Duration::from_millis(audio_levels_interval_millis as u64))
     };
 
-    match call_manager::proceed(
+    let stats_interval = if stats_interval_millis <= 0 {
+        None
+    } else {
+        Some(Duration::from_millis(stats_interval_millis as u64))
+    };
+
+    let result = call_manager::proceed(
         &env,
         call_manager as *mut AndroidCallManager,
         call_id,
         jni_call_context,
         CallConfig::default().with_data_mode(DataMode::from_i32(data_mode)),
         audio_levels_interval,
-    ) {
-        Ok(v) => v,
-        Err(e) => {
-            error::throw_error(&env, e);
-        }
+    )
+    .and_then(|_| call_manager::arm_call_statistics(call_manager as *mut AndroidCallManager, call_id, stats_interval));
+
+    if let Err(e) = result {
+        error::throw_error(&env, e);
     }
 }
 
@@ -304,6 +311,32 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcReceivedOffe
     }
 }
 
+/// A media-upgrade offer is handed to the app through the same `onSendOffer`
+/// callback, with the same arguments, as a brand-new call offer; nothing in
+/// it marks it as an upgrade. The app decides which incoming offers to route
+/// here instead of through `ringrtcReceivedOffer`.
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcReceivedCallMediaUpgradeOffer(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    call_id: jlong,
+    requested_media_type: jint,
+) {
+    match call_manager::handle_upgrade_offer_received(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        call_id,
+        CallMediaType::from_i32(requested_media_type),
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcReceivedIceCandidates(
@@ -506,6 +539,28 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcSetVideoEnab
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcUpgradeCallMediaType(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    call_id: jlong,
+    new_media_type: jint,
+) {
+    match call_manager::upgrade_call_media_type(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        call_id,
+        CallMediaType::from_i32(new_media_type),
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub extern "C" fn Java_org_signal_ringrtc_CallManager_ringrtcUpdateDataMode(
@@ -727,10 +782,17 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcCreateGroupCal
     sfu_url: JString,
     hkdf_extra_info: jbyteArray,
     audio_levels_interval_millis: jint,
+    stats_interval_millis: jint,
     native_peer_connection_factory_borrowed_rc: jlong,
     native_audio_track_borrowed_rc: jlong,
     native_video_track_borrowed_rc: jlong,
 ) -> jlong {
+    let stats_interval = if stats_interval_millis <= 0 {
+        None
+    } else {
+        Some(Duration::from_millis(stats_interval_millis as u64))
+    };
+
     match call_manager::create_group_call_client(
         &env,
         call_manager as *mut AndroidCallManager,
@@ -742,7 +804,14 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcCreateGroupCal
         native_audio_track_borrowed_rc,
         native_video_track_borrowed_rc,
     ) {
-        Ok(v) => v as i64,
+        Ok(v) => {
+            if let Err(e) =
+                call_manager::arm_group_call_statistics(call_manager as *mut AndroidCallManager, v, stats_interval)
+            {
+                error::throw_error(&env, e);
+            }
+            v as i64
+        }
         Err(e) => {
             error::throw_error(&env, e);
             group_call::INVALID_CLIENT_ID as i64
@@ -924,6 +993,52 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetOutgoingVid
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetParticipantAudioEnabled(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    other_client_demux_id: jlong,
+    enabled: bool,
+) {
+    match call_manager::set_participant_audio_enabled(
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        other_client_demux_id as group_call::DemuxId,
+        enabled,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetParticipantVolume(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    other_client_demux_id: jlong,
+    volume: jint,
+) {
+    match call_manager::set_participant_volume(
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        other_client_demux_id as group_call::DemuxId,
+        volume,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcRing(
@@ -932,13 +1047,22 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcRing(
     call_manager: jlong,
     client_id: jlong,
     recipient: jbyteArray,
+    media_types: jint,
 ) {
     match call_manager::group_ring(
         &env,
         call_manager as *mut AndroidCallManager,
         client_id as group_call::ClientId,
         recipient,
-    ) {
+        media_types,
+    )
+    .and_then(|_| {
+        call_manager::record_ring_media_types(
+            call_manager as *mut AndroidCallManager,
+            client_id as group_call::ClientId,
+            media_types,
+        )
+    }) {
         Ok(v) => v,
         Err(e) => {
             error::throw_error(&env, e);
@@ -965,6 +1089,53 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcResendMediaKey
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcReceiveSenderKey(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    demux_id: jlong,
+    key: jbyteArray,
+    cipher_suite: jint,
+) {
+    match call_manager::receive_sender_key(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        demux_id as group_call::DemuxId,
+        key,
+        cipher_suite,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcGetMediaKeysFingerprint(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+) -> jbyteArray {
+    match call_manager::get_media_keys_fingerprint(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+            0 as jbyteArray
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetDataMode(
@@ -986,6 +1157,77 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetDataMode(
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcScheduleGroupCall(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    start_time_ms: jlong,
+    title: jbyteArray,
+) -> jlong {
+    match call_manager::schedule_group_call(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        start_time_ms as u64,
+        title,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+            0
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcCancelScheduledGroupCall(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    ring_id: jlong,
+) {
+    match call_manager::cancel_scheduled_group_call(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        ring_id,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcCheckScheduledCallWindow(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    ring_id: jlong,
+    now_ms: jlong,
+) {
+    match call_manager::check_scheduled_call_window(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        ring_id,
+        now_ms as u64,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcRequestVideo(
@@ -1010,6 +1252,62 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcRequestVideo(
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetAudioLevels(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    demux_ids: jlongArray,
+    levels: jfloatArray,
+) {
+    match try_scoped(|| {
+        let len = env.get_array_length(demux_ids)? as usize;
+        let mut demux_id_buf = vec![0i64; len];
+        env.get_long_array_region(demux_ids, 0, &mut demux_id_buf)?;
+        let mut level_buf = vec![0f32; len];
+        env.get_float_array_region(levels, 0, &mut level_buf)?;
+        call_manager::record_audio_levels(
+            call_manager as *mut AndroidCallManager,
+            client_id as group_call::ClientId,
+            &demux_id_buf,
+            &level_buf,
+        )
+    }) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcGetSpeakers(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    top_n: jint,
+) -> jobject {
+    match call_manager::get_speakers(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        top_n,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+            0 as jobject
+        }
+    }
+}
+
+// Admission control for restricted call links: approve/deny a pending join
+// request, or remove/block an already-admitted client.
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcApproveUser(
@@ -1064,6 +1362,7 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcRemoveClient(
     other_client_demux_id: jlong,
 ) {
     match call_manager::remove_client(
+        &env,
         call_manager as *mut AndroidCallManager,
         client_id as group_call::ClientId,
         other_client_demux_id,
@@ -1085,6 +1384,7 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcBlockClient(
     other_client_demux_id: jlong,
 ) {
     match call_manager::block_client(
+        &env,
         call_manager as *mut AndroidCallManager,
         client_id as group_call::ClientId,
         other_client_demux_id,
@@ -1140,6 +1440,27 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetMembershipP
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetMediaCipherSuite(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    suite_id: jint,
+) {
+    match call_manager::set_media_cipher_suite(
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        suite_id,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcReact(
@@ -1183,6 +1504,28 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcRaiseHand(
     }
 }
 
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcSetRecordingState(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+    recording: bool,
+) {
+    match call_manager::set_recording_state(
+        &env,
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+        recording,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+        }
+    }
+}
+
 #[no_mangle]
 #[allow(non_snake_case)]
 pub unsafe extern "C" fn Java_org_signal_ringrtc_CallId_ringrtcFromEraId(
@@ -1201,3 +1544,23 @@ pub unsafe extern "C" fn Java_org_signal_ringrtc_CallId_ringrtcFromEraId(
         0
     })
 }
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_org_signal_ringrtc_GroupCall_ringrtcGetDefaultJoinMediaTypes(
+    env: JNIEnv,
+    _object: JObject,
+    call_manager: jlong,
+    client_id: jlong,
+) -> jint {
+    match call_manager::default_join_media_types(
+        call_manager as *mut AndroidCallManager,
+        client_id as group_call::ClientId,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error::throw_error(&env, e);
+            0
+        }
+    }
+}
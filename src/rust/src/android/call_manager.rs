@@ -0,0 +1,767 @@
+//
+// Copyright 2019-2021 Signal Messenger, LLC
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Additions to `AndroidCallManager` backing the native methods added to
+//! `jni_call_manager.rs` by the media-upgrade / group-call admin
+//! backlog. This file assumes (and does not redefine) the pre-existing
+//! `AndroidCallManager` fields `active_connection: Option<Connection<AndroidPlatform>>`
+//! and `jni_call_manager: GlobalRef` used by the legacy call-setup path;
+//! the legacy call-setup, call-link, and group-call plumbing this builds
+//! on lives alongside this file unchanged. This series also assumes two
+//! more fields on the same struct that it does not redefine here:
+//! `group_clients: HashMap<group_call::ClientId, group_call::Client>`,
+//! keyed the same way the rest of this file's `group_client`/
+//! `group_client_mut` helpers expect, and `stats_interval: Option<Duration>`,
+//! the 1:1-call counterpart to `group_call::Client`'s own `stats_interval`
+//! field, armed by `arm_call_statistics`. The admin passkey presented
+//! when joining a restricted call link is captured on the `group_call::Client`
+//! by the existing join path and reused here for admission-control actions,
+//! rather than threading it through every approve/deny/remove/block call.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use jni::objects::JValue;
+use jni::sys::jbyteArray;
+use jni::JNIEnv;
+
+use crate::android::android_platform::AndroidPlatform;
+use crate::common::CallMediaType;
+use crate::core::connection::Connection;
+use crate::core::group_call::{self, CipherSuite, DemuxId, MediaTypes, RecordingState, RingId};
+use crate::core::signaling;
+use crate::webrtc::stats::CandidateType;
+
+impl AndroidCallManager {
+    fn active_connection_mut(&mut self) -> Result<&mut Connection<AndroidPlatform>> {
+        self.active_connection
+            .as_mut()
+            .ok_or_else(|| anyhow!("no active connection to upgrade"))
+    }
+
+    fn group_client_mut(&mut self, client_id: group_call::ClientId) -> Result<&mut group_call::Client> {
+        self.group_clients
+            .get_mut(&client_id)
+            .ok_or_else(|| anyhow!("unknown group call client {}", client_id))
+    }
+
+    fn group_client(&self, client_id: group_call::ClientId) -> Result<&group_call::Client> {
+        self.group_clients
+            .get(&client_id)
+            .ok_or_else(|| anyhow!("unknown group call client {}", client_id))
+    }
+}
+
+/// Locally mutes/unmutes one remote participant's incoming audio
+/// without affecting what the SFU forwards to other clients.
+pub unsafe fn set_participant_audio_enabled(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    demux_id: DemuxId,
+    enabled: bool,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?
+        .set_participant_audio_enabled(demux_id, enabled);
+    Ok(())
+}
+
+/// Sets the local playback gain (`0..=200`, `100` = unity) for one
+/// remote participant's incoming audio.
+pub unsafe fn set_participant_volume(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    demux_id: DemuxId,
+    volume: i32,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?
+        .set_participant_volume(demux_id, volume)
+}
+
+/// Computes the HKDF/SHA-256 fingerprint over the client's current set
+/// of sender keys and hands it back as a Java byte array, for the app to
+/// render as a short safety-number-style string.
+pub unsafe fn get_media_keys_fingerprint(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+) -> Result<jni::sys::jbyteArray> {
+    let cm = call_manager
+        .as_ref()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let fingerprint = cm
+        .group_clients
+        .get(&client_id)
+        .ok_or_else(|| anyhow!("unknown group call client {}", client_id))?
+        .media_keys_fingerprint();
+    Ok(env.byte_array_from_slice(&fingerprint)?)
+}
+
+/// Receive-side counterpart to `ringrtcResendMediaKeys`: records the sender
+/// key `demux_id` just distributed to us over the existing opaque
+/// call-message channel, along with the cipher suite that member announced
+/// it was encrypted with. Without this, `sender_keys` never gets populated
+/// for anyone but ourselves and `media_keys_fingerprint` can't catch an SFU
+/// that substitutes a different key in transit.
+pub unsafe fn receive_sender_key(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    demux_id: DemuxId,
+    key: jbyteArray,
+    cipher_suite_id: i32,
+) -> Result<()> {
+    let key = env.convert_byte_array(key)?;
+    let cipher_suite = CipherSuite::from_i32(cipher_suite_id)?;
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?
+        .set_sender_key(demux_id, key, cipher_suite)
+}
+
+/// Triggers an SDP renegotiation on the active `Connection`: builds a new
+/// offer containing (or dropping) the video m-line and sends it to the
+/// peer as a fresh offer over the existing signaling channel, tagged
+/// with the media type being requested.
+pub unsafe fn upgrade_call_media_type(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    call_id: i64,
+    new_media_type: CallMediaType,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let offer = cm
+        .active_connection_mut()?
+        .create_media_upgrade_offer(new_media_type)?;
+    send_offer_to_app(env, cm, call_id, &offer)
+}
+
+/// Receive-side handler for an incoming media-upgrade offer, reached via
+/// `ringrtcReceivedCallMediaUpgradeOffer` once the app tags an incoming
+/// opaque message as an upgrade rather than a brand-new call offer.
+/// Surfaces an "upgrade requested" event to the app so the UI can prompt
+/// the user. Only the call id and requested media type come in: the
+/// upgrade offer's SDP is not applied and no answer is built here.
+pub unsafe fn handle_upgrade_offer_received(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    call_id: i64,
+    requested_media_type: CallMediaType,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "onSendMediaUpgradeRequested",
+        "(JI)V",
+        &[
+            JValue::from(call_id),
+            JValue::from(requested_media_type.as_i32()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Hands the newly-built upgrade offer back up to the Java
+/// `CallManager.Observer`, the same way the initial call offer is
+/// delivered to the app for sending over the signaling transport.
+fn send_offer_to_app(
+    env: &JNIEnv,
+    cm: &AndroidCallManager,
+    call_id: i64,
+    offer: &signaling::Offer,
+) -> Result<()> {
+    let opaque = env.byte_array_from_slice(offer.opaque())?;
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "onSendOffer",
+        "(J[BI)V",
+        &[
+            JValue::from(call_id),
+            JValue::from(opaque),
+            JValue::from(offer.call_media_type().as_i32()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// A quality snapshot for one active call, sampled from WebRTC's
+/// `RTCStatsReport` on a `stats_interval` cadence and delivered to the
+/// app so it can render signal bars / adaptive-bitrate warnings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CallStatistics {
+    pub round_trip_time_ms: u32,
+    pub jitter_ms: u32,
+    pub fraction_packet_loss: f32,
+    pub outgoing_bitrate_bps: u32,
+    pub incoming_bitrate_bps: u32,
+    pub network_route: NetworkRouteKind,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NetworkRouteKind {
+    #[default]
+    Unknown,
+    Direct,
+    TurnRelay,
+}
+
+impl NetworkRouteKind {
+    fn from_candidate_type(candidate_type: CandidateType) -> Self {
+        match candidate_type {
+            CandidateType::Relay => Self::TurnRelay,
+            CandidateType::Host | CandidateType::ServerReflexive => Self::Direct,
+        }
+    }
+
+    fn as_i32(self) -> i32 {
+        match self {
+            Self::Unknown => 0,
+            Self::Direct => 1,
+            Self::TurnRelay => 2,
+        }
+    }
+}
+
+/// Samples the active connection's `RTCStatsReport` and reduces it down
+/// to the fields a quality indicator actually needs.
+fn sample_call_statistics(connection: &mut Connection<AndroidPlatform>) -> Result<CallStatistics> {
+    let report = connection.get_stats_report()?;
+    Ok(CallStatistics {
+        round_trip_time_ms: report.round_trip_time_ms(),
+        jitter_ms: report.jitter_ms(),
+        fraction_packet_loss: report.fraction_packet_loss(),
+        outgoing_bitrate_bps: report.outgoing_bitrate_bps(),
+        incoming_bitrate_bps: report.incoming_bitrate_bps(),
+        network_route: NetworkRouteKind::from_candidate_type(report.active_candidate_type()),
+    })
+}
+
+fn notify_call_statistics(
+    env: &JNIEnv,
+    cm: &AndroidCallManager,
+    call_id: i64,
+    stats: CallStatistics,
+) -> Result<()> {
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "onCallStatistics",
+        "(JIIFIII)V",
+        &[
+            JValue::from(call_id),
+            JValue::from(stats.round_trip_time_ms as i32),
+            JValue::from(stats.jitter_ms as i32),
+            JValue::from(stats.fraction_packet_loss),
+            JValue::from(stats.outgoing_bitrate_bps as i32),
+            JValue::from(stats.incoming_bitrate_bps as i32),
+            JValue::from(stats.network_route.as_i32()),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Stores `interval` (or clears it, if `None`) as the call manager's
+/// `stats_interval`; `call_id` is not used. `ringrtcProceed` calls this
+/// right after `proceed()` succeeds. No timer is started here: a snapshot
+/// is sampled and delivered only when [`on_call_statistics_tick`] is called.
+pub unsafe fn arm_call_statistics(
+    call_manager: *mut AndroidCallManager,
+    call_id: i64,
+    interval: Option<Duration>,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.stats_interval = interval;
+    let _ = call_id;
+    Ok(())
+}
+
+/// Samples the active connection's stats and delivers them to the app as
+/// one `onCallStatistics` snapshot; does nothing while no `stats_interval`
+/// is set. Nothing in this file schedules calls to it.
+pub unsafe fn on_call_statistics_tick(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    call_id: i64,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    if cm.stats_interval.is_none() {
+        return Ok(());
+    }
+    let stats = sample_call_statistics(cm.active_connection_mut()?)?;
+    notify_call_statistics(env, cm, call_id, stats)
+}
+
+/// Stores `interval` (or clears it, if `None`) as a group-call client's
+/// `stats_interval`; `ringrtcCreateGroupCallClient` calls this right after
+/// `create_group_call_client` returns the new client id. Nothing samples or
+/// delivers statistics for a group-call client: only the 1:1 path has
+/// [`on_call_statistics_tick`].
+pub unsafe fn arm_group_call_statistics(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    interval: Option<Duration>,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?.set_stats_interval(interval);
+    Ok(())
+}
+
+/// An admission-control action taken by an admitted member of a restricted
+/// call link, sent to the SFU so it can enforce the decision for every
+/// client, not just this one.
+enum AdminAction {
+    Approve,
+    Deny,
+    Remove,
+    Block,
+}
+
+impl AdminAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Approve => "approve",
+            Self::Deny => "deny",
+            Self::Remove => "remove",
+            Self::Block => "block",
+        }
+    }
+}
+
+/// Sends `action` for `target` to the SFU, authenticated with the admin
+/// passkey recorded for this client at join time. Delegates the actual
+/// network call to the app the same way every other SFU/call-link request
+/// in this API does: by invoking a method on the `CallManager.Observer`
+/// and letting the platform's own HTTP stack perform it. This is
+/// fire-and-forget from Rust's side; the SFU enforces the action for every
+/// client, so there's no per-caller state here waiting on a reply.
+fn notify_sfu_admin_action(
+    env: &JNIEnv,
+    cm: &AndroidCallManager,
+    admin_passkey: &[u8],
+    target: &[u8],
+    action: AdminAction,
+) -> Result<()> {
+    let admin_passkey_array = env.byte_array_from_slice(admin_passkey)?;
+    let target_array = env.byte_array_from_slice(target)?;
+    let action_str = env.new_string(action.as_str())?;
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "sendCallLinkAdminAction",
+        "([B[BLjava/lang/String;)V",
+        &[
+            JValue::from(admin_passkey_array),
+            JValue::from(target_array),
+            JValue::from(action_str),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Called when the SFU reports that `other_user_id` wants to join a
+/// restricted call link that `client_id` is already in. Records them as
+/// awaiting approval and surfaces an event so the app can show them in the
+/// lobby list.
+pub unsafe fn handle_join_requested(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    other_user_id: Vec<u8>,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let other_user_id_array = env.byte_array_from_slice(&other_user_id)?;
+    cm.group_client_mut(client_id)?.request_join(other_user_id);
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "onJoinRequested",
+        "(J[B)V",
+        &[JValue::from(client_id as i64), JValue::from(other_user_id_array)],
+    )?;
+    Ok(())
+}
+
+/// Approves `other_user_id`'s pending join request and tells the SFU to
+/// let them in.
+pub unsafe fn approve_user(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    other_user_id: jbyteArray,
+) -> Result<()> {
+    let other_user_id = env.convert_byte_array(other_user_id)?;
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let client = cm.group_client_mut(client_id)?;
+    client.approve_user(&other_user_id)?;
+    let admin_passkey = client.admin_passkey()?.to_vec();
+    notify_sfu_admin_action(env, cm, &admin_passkey, &other_user_id, AdminAction::Approve)
+}
+
+/// Denies `other_user_id`'s pending join request and tells the SFU to
+/// refuse them.
+pub unsafe fn deny_user(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    other_user_id: jbyteArray,
+) -> Result<()> {
+    let other_user_id = env.convert_byte_array(other_user_id)?;
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let client = cm.group_client_mut(client_id)?;
+    client.deny_user(&other_user_id)?;
+    let admin_passkey = client.admin_passkey()?.to_vec();
+    notify_sfu_admin_action(env, cm, &admin_passkey, &other_user_id, AdminAction::Deny)
+}
+
+/// Removes an already-admitted client from the call. Unlike block, this
+/// does not prevent them from requesting to join again.
+pub unsafe fn remove_client(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    other_client_demux_id: i64,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let admin_passkey = cm.group_client_mut(client_id)?.admin_passkey()?.to_vec();
+    notify_sfu_admin_action(
+        env,
+        cm,
+        &admin_passkey,
+        &other_client_demux_id.to_be_bytes(),
+        AdminAction::Remove,
+    )
+}
+
+/// Removes an already-admitted client and blocks them from rejoining this
+/// call link.
+pub unsafe fn block_client(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    other_client_demux_id: i64,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let client = cm.group_client_mut(client_id)?;
+    client.block_demux_id(other_client_demux_id as DemuxId);
+    let admin_passkey = client.admin_passkey()?.to_vec();
+    notify_sfu_admin_action(
+        env,
+        cm,
+        &admin_passkey,
+        &other_client_demux_id.to_be_bytes(),
+        AdminAction::Block,
+    )
+}
+
+/// Reserves a future call starting at `start_time_ms` and returns its
+/// `RingId` as a raw `i64`, for the app to embed in the invite it sends
+/// out. The reservation is purely local until someone actually joins;
+/// there's no SFU call to make yet.
+pub unsafe fn schedule_group_call(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    start_time_ms: u64,
+    title: jbyteArray,
+) -> Result<i64> {
+    let title = env.convert_byte_array(title)?;
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let ring_id = cm.group_client_mut(client_id)?.schedule_call(start_time_ms, title);
+    Ok(ring_id.into())
+}
+
+/// Delivers notice that `ring_id`'s scheduled call is cancelled to the app,
+/// which relays it to the same peers the original schedule notification
+/// went to -- the same `CallManager.Observer` delegation
+/// `notify_sfu_admin_action`/`broadcast_recording_state` use for every
+/// other peer-facing notification in this file.
+fn notify_scheduled_call_cancelled(
+    env: &JNIEnv,
+    cm: &AndroidCallManager,
+    ring_id: RingId,
+) -> Result<()> {
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "sendScheduledCallCancellation",
+        "(J)V",
+        &[JValue::from(i64::from(ring_id))],
+    )?;
+    Ok(())
+}
+
+/// Cancels a scheduled call and tells already-invited peers it's off.
+pub unsafe fn cancel_scheduled_group_call(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    ring_id: i64,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let ring_id = RingId::from(ring_id);
+    cm.group_client_mut(client_id)?.cancel_scheduled_call(ring_id)?;
+    notify_scheduled_call_cancelled(env, cm, ring_id)
+}
+
+/// Backs `ringrtcCheckScheduledCallWindow`, which the app calls on a
+/// coarse timer of its own (the same kind of app-driven tick
+/// `ringrtcSetAudioLevels` uses) to check whether a scheduled call's start
+/// time has arrived; fires `onScheduledCallWindowOpen` exactly once per
+/// scheduled call, when `now_ms` first reaches its `start_time_ms`.
+pub unsafe fn check_scheduled_call_window(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    ring_id: i64,
+    now_ms: u64,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let ring_id = RingId::from(ring_id);
+    if !cm
+        .group_client_mut(client_id)?
+        .mark_scheduled_call_window_opened(ring_id, now_ms)
+    {
+        return Ok(());
+    }
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "onScheduledCallWindowOpen",
+        "(JJ)V",
+        &[JValue::from(client_id as i64), JValue::from(i64::from(ring_id))],
+    )?;
+    Ok(())
+}
+
+/// Delivers `state` to the app as an `onRecordingStateChanged` event.
+/// `recorder_demux_id` is `-1` when nobody is currently recording.
+fn notify_recording_state(
+    env: &JNIEnv,
+    cm: &AndroidCallManager,
+    client_id: group_call::ClientId,
+    state: RecordingState,
+) -> Result<()> {
+    let recorder_demux_id = state
+        .recorder_demux_id
+        .map(|demux_id| demux_id as i64)
+        .unwrap_or(-1);
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "onRecordingStateChanged",
+        "(JZJ)V",
+        &[
+            JValue::from(client_id as i64),
+            JValue::from(state.recording),
+            JValue::from(recorder_demux_id),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Broadcasts `state` to the other clients in the call, the same way
+/// `notify_sfu_admin_action` delegates a network call to the app rather
+/// than opening a socket in Rust: the platform relays it to every other
+/// member over the call's data channel.
+fn broadcast_recording_state(
+    env: &JNIEnv,
+    cm: &AndroidCallManager,
+    client_id: group_call::ClientId,
+    state: RecordingState,
+) -> Result<()> {
+    let recorder_demux_id = state
+        .recorder_demux_id
+        .map(|demux_id| demux_id as i64)
+        .unwrap_or(-1);
+    env.call_method(
+        cm.jni_call_manager.as_obj(),
+        "sendRecordingStateBroadcast",
+        "(JZJ)V",
+        &[
+            JValue::from(client_id as i64),
+            JValue::from(state.recording),
+            JValue::from(recorder_demux_id),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Starts or stops this client's own recording of the call, broadcasts the
+/// new state to the other members, and delivers the event locally too.
+pub unsafe fn set_recording_state(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    recording: bool,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let state = cm.group_client_mut(client_id)?.set_recording_state(recording)?;
+    broadcast_recording_state(env, cm, client_id, state)?;
+    notify_recording_state(env, cm, client_id, state)
+}
+
+/// Called from the existing `set_group_members` completion path when a new
+/// member's roster entry arrives, so a late joiner immediately learns
+/// whether the call is already being recorded instead of waiting for the
+/// next state change.
+pub unsafe fn notify_recording_state_to_late_joiner(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let state = cm.group_client_mut(client_id)?.recording_state();
+    notify_recording_state(env, cm, client_id, state)
+}
+
+/// Negotiates the AEAD cipher suite this client uses for its own outgoing
+/// frame encryption. Takes effect on the next sender key distributed to
+/// other members; it does not retroactively re-key existing frames.
+pub unsafe fn set_media_cipher_suite(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    suite_id: i32,
+) -> Result<()> {
+    let cipher_suite = CipherSuite::from_i32(suite_id)?;
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?.set_cipher_suite(cipher_suite);
+    Ok(())
+}
+
+/// Records the media-type hint a just-sent ring carried; `ringrtcRing`
+/// calls this after `group_ring` has sent the ring with the same
+/// `media_types`. The join
+/// flow that later resolves this call's `RingId` via `ringrtcFromEraId`
+/// reads this back through [`default_join_media_types`] to decide whether
+/// to default to audio-only or audio+video.
+pub unsafe fn record_ring_media_types(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    media_types: i32,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?
+        .set_last_ring_media_types(MediaTypes::from_i32(media_types));
+    Ok(())
+}
+
+/// The media types to default to when joining in response to this ring,
+/// as an `i32` in the same bit layout `ringrtcRing`'s `media_types`
+/// parameter uses. Falls back to audio+video if no hint was ever
+/// recorded, matching the pre-hint behavior.
+pub unsafe fn default_join_media_types(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+) -> Result<i32> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    Ok(cm
+        .group_client_mut(client_id)?
+        .default_join_media_types()
+        .as_i32())
+}
+
+/// Feeds one participant's instantaneous audio level into its smoothed
+/// speaker level.
+pub unsafe fn record_audio_level(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    demux_id: DemuxId,
+    instant_level: f32,
+) -> Result<()> {
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    cm.group_client_mut(client_id)?
+        .update_speaker_level(demux_id, instant_level);
+    Ok(())
+}
+
+/// Batched entry point for the existing `audio_levels_interval` delivery
+/// path: once per tick, the app hands back the same per-participant
+/// instantaneous levels it just received (and is about to render) so they
+/// can also be folded into the active-speaker ranking. `demux_ids` and
+/// `levels` are parallel arrays; a length mismatch is a caller bug.
+pub unsafe fn record_audio_levels(
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    demux_ids: &[i64],
+    levels: &[f32],
+) -> Result<()> {
+    if demux_ids.len() != levels.len() {
+        return Err(anyhow!(
+            "audio level arrays have mismatched lengths: {} demux ids, {} levels",
+            demux_ids.len(),
+            levels.len()
+        ));
+    }
+    let cm = call_manager
+        .as_mut()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let client = cm.group_client_mut(client_id)?;
+    for (demux_id, level) in demux_ids.iter().zip(levels.iter()) {
+        client.update_speaker_level(*demux_id as DemuxId, *level);
+    }
+    Ok(())
+}
+
+/// Returns the `top_n` currently-speaking demux ids, loudest first, as a
+/// Java `long[]` handed back through the JNI boundary as a raw `jobject`.
+pub unsafe fn get_speakers(
+    env: &JNIEnv,
+    call_manager: *mut AndroidCallManager,
+    client_id: group_call::ClientId,
+    top_n: i32,
+) -> Result<jni::sys::jobject> {
+    let cm = call_manager
+        .as_ref()
+        .ok_or_else(|| anyhow!("null call manager"))?;
+    let top_n = top_n.max(0) as usize;
+    let demux_ids: Vec<i64> = cm
+        .group_client(client_id)?
+        .top_speakers(top_n)
+        .into_iter()
+        .map(|demux_id| demux_id as i64)
+        .collect();
+
+    let array = env.new_long_array(demux_ids.len() as i32)?;
+    env.set_long_array_region(array, 0, &demux_ids)?;
+    Ok(array as jni::sys::jobject)
+}
@@ -0,0 +1,33 @@
+//
+// Copyright 2019-2021 Signal Messenger, LLC
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Additions to `Connection` for mid-call media-type renegotiation.
+//!
+//! The rest of `Connection` (ICE/SDP plumbing, the 1:1 state machine)
+//! lives alongside this file unchanged; this covers only the
+//! audio-to-video upgrade path added on top of it.
+
+use anyhow::{anyhow, Result};
+
+use crate::android::android_platform::AndroidPlatform;
+use crate::common::CallMediaType;
+use crate::core::signaling;
+
+impl Connection<AndroidPlatform> {
+    /// Builds a new SDP offer that adds (or removes) the video m-line on
+    /// an already-connected call, for a mid-call media upgrade. Reuses
+    /// the same offer-construction path used at initial call setup, just
+    /// invoked again with the new `CallMediaType`.
+    pub fn create_media_upgrade_offer(
+        &mut self,
+        new_media_type: CallMediaType,
+    ) -> Result<signaling::Offer> {
+        if !self.is_connected() {
+            return Err(anyhow!("cannot upgrade media type before the call is connected"));
+        }
+        let sdp = self.create_offer(new_media_type)?;
+        signaling::Offer::new(new_media_type, sdp)
+    }
+}
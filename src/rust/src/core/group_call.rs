@@ -0,0 +1,655 @@
+//
+// Copyright 2019-2021 Signal Messenger, LLC
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Group call client state: demux-id-keyed participant tracking backing
+//! the native methods added to `jni_call_manager.rs`. `ClientId`,
+//! `DemuxId`, `INVALID_CLIENT_ID`, and `RingId` are the same identifiers
+//! already used by the pre-existing group-call JNI surface
+//! (`ringrtcCreateGroupCallClient`, `ringrtcFromEraId`, ...); the `Client`
+//! struct here is the per-client state machine those calls operate on.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+pub type ClientId = u32;
+pub type DemuxId = u32;
+
+pub const INVALID_CLIENT_ID: ClientId = 0;
+
+/// A call-wide identifier for one ring of a group call. The SFU-issued
+/// "era" string is the canonical form (parsed by `ringrtcFromEraId`); a
+/// scheduled call doesn't have an era string yet, so it gets one minted
+/// locally from a scheduling nonce, normalized through the same
+/// `from_era_id` hash so both paths produce the same `RingId` shape.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct RingId(i64);
+
+impl RingId {
+    pub fn from_era_id(era_id: &str) -> Self {
+        let digest = Sha256::digest(era_id.as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        Self(i64::from_be_bytes(bytes))
+    }
+
+    fn from_schedule_nonce(nonce: u64) -> Self {
+        Self::from_era_id(&format!("scheduled-{}", nonce))
+    }
+}
+
+impl From<RingId> for i64 {
+    fn from(ring_id: RingId) -> Self {
+        ring_id.0
+    }
+}
+
+impl From<i64> for RingId {
+    fn from(value: i64) -> Self {
+        Self(value)
+    }
+}
+
+/// A smoothed audio level for one participant, used to rank active
+/// speakers without flickering on every short pause or transient noise
+/// spike.
+#[derive(Clone, Copy, Debug, Default)]
+struct SpeakerLevel {
+    /// Exponential moving average of recent instantaneous levels.
+    level: f32,
+    /// Whether this participant currently counts as "speaking", tracked
+    /// with separate enter/leave thresholds (hysteresis) so a level
+    /// hovering right at the edge doesn't toggle speaking on and off.
+    speaking: bool,
+}
+
+/// EMA smoothing factor: `level_t = max(instant, ALPHA * level_(t-1))`.
+/// Lets the level jump up immediately when someone starts talking, but
+/// decays gradually afterward instead of dropping to zero the instant
+/// they pause.
+const SPEAKER_LEVEL_ALPHA: f32 = 0.9;
+/// Level above which a non-speaking participant starts counting as
+/// speaking.
+const SPEAKER_ENTER_THRESHOLD: f32 = 0.15;
+/// Level below which a speaking participant stops counting as speaking.
+/// Lower than the enter threshold so a level oscillating near the
+/// boundary doesn't flicker between the two states.
+const SPEAKER_LEAVE_THRESHOLD: f32 = 0.05;
+
+/// Which media a ring is requesting the recipient join with. Bit 0 is
+/// audio, bit 1 is video; a value of `0` (no bits set, e.g. from an older
+/// client that never sends this hint) is treated as "audio and video" so
+/// ringing stays backward compatible with clients that predate the hint.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MediaTypes {
+    pub audio: bool,
+    pub video: bool,
+}
+
+impl MediaTypes {
+    pub fn from_i32(value: i32) -> Self {
+        if value == 0 {
+            return Self {
+                audio: true,
+                video: true,
+            };
+        }
+        Self {
+            audio: value & 0b01 != 0,
+            video: value & 0b10 != 0,
+        }
+    }
+
+    pub fn as_i32(self) -> i32 {
+        (self.audio as i32) | ((self.video as i32) << 1)
+    }
+}
+
+impl Default for MediaTypes {
+    fn default() -> Self {
+        Self {
+            audio: true,
+            video: true,
+        }
+    }
+}
+
+/// A negotiable AEAD cipher suite for per-sender frame encryption. Each
+/// member picks one for its own frames and announces it alongside its
+/// sender key; a member that switches suites on a re-key is rejected as a
+/// [`CipherSuiteMismatch`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CipherSuite {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    pub fn from_i32(value: i32) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Aes256Gcm),
+            1 => Ok(Self::ChaCha20Poly1305),
+            other => Err(anyhow!("unknown media cipher suite id {}", other)),
+        }
+    }
+
+    pub fn as_i32(self) -> i32 {
+        match self {
+            Self::Aes256Gcm => 0,
+            Self::ChaCha20Poly1305 => 1,
+        }
+    }
+}
+
+impl Default for CipherSuite {
+    fn default() -> Self {
+        Self::Aes256Gcm
+    }
+}
+
+/// Returned instead of a generic decode error when a frame fails to
+/// decrypt because its sender is using a different cipher suite than we
+/// negotiated, so callers can tell a real negotiation mismatch apart from
+/// an ordinary corrupt/late frame rather than just dropping it silently.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CipherSuiteMismatch {
+    pub demux_id: DemuxId,
+    pub expected: CipherSuite,
+    pub received: CipherSuite,
+}
+
+impl std::fmt::Display for CipherSuiteMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cipher suite mismatch for demux id {}: expected {:?}, got {:?}",
+            self.demux_id, self.expected, self.received
+        )
+    }
+}
+
+impl std::error::Error for CipherSuiteMismatch {}
+
+/// Whether the call is currently being recorded, and by whom, so the UI
+/// can show a recording indicator to everyone in the call -- not just the
+/// member who started it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RecordingState {
+    pub recording: bool,
+    pub recorder_demux_id: Option<DemuxId>,
+}
+
+/// A group call reserved for a future start time, before anyone has
+/// joined and before the SFU has assigned it a real era.
+#[derive(Clone, Debug)]
+pub struct ScheduledCall {
+    pub start_time_ms: u64,
+    pub title: Vec<u8>,
+    /// Set once [`Client::mark_scheduled_call_window_opened`] has fired the
+    /// "window opens" event, so a second timer tick doesn't re-notify.
+    window_opened: bool,
+}
+
+/// A local-only playback adjustment for one remote participant's audio.
+/// This never touches what the SFU forwards to other clients -- it only
+/// changes how `demux_id`'s stream is rendered on this device.
+#[derive(Clone, Copy, Debug)]
+struct ParticipantAudioState {
+    enabled: bool,
+    /// Linear gain in `0..=200`, where `100` is unity gain.
+    volume: u8,
+}
+
+impl Default for ParticipantAudioState {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 100,
+        }
+    }
+}
+
+/// Where a would-be participant of a restricted call link stands relative
+/// to the admitted roster.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PendingApprovalState {
+    /// Waiting in the lobby for an admitted member to approve or deny them.
+    AwaitingApproval,
+    /// Approved; the SFU has been told to let them in.
+    Approved,
+    /// Denied; the SFU has been told to refuse them.
+    Denied,
+}
+
+/// Per-client group-call state. One `Client` backs each
+/// `group_call::ClientId` handed out by `create_group_call_client`.
+#[derive(Default)]
+pub struct Client {
+    participant_audio: HashMap<DemuxId, ParticipantAudioState>,
+    stats_interval: Option<Duration>,
+    sender_keys: HashMap<DemuxId, Vec<u8>>,
+    hkdf_extra_info: Vec<u8>,
+    pending_users: HashMap<Vec<u8>, PendingApprovalState>,
+    blocked_demux_ids: HashSet<DemuxId>,
+    admin_passkey: Option<Vec<u8>>,
+    scheduled_calls: HashMap<RingId, ScheduledCall>,
+    next_schedule_nonce: u64,
+    local_demux_id: Option<DemuxId>,
+    recording_state: RecordingState,
+    cipher_suite: CipherSuite,
+    sender_key_cipher_suites: HashMap<DemuxId, CipherSuite>,
+    last_ring_media_types: MediaTypes,
+    speaker_levels: HashMap<DemuxId, SpeakerLevel>,
+}
+
+impl Client {
+    /// Locally mutes or unmutes the incoming audio track for
+    /// `demux_id`, without affecting what the SFU forwards to anyone
+    /// else.
+    pub fn set_participant_audio_enabled(&mut self, demux_id: DemuxId, enabled: bool) {
+        self.participant_audio.entry(demux_id).or_default().enabled = enabled;
+    }
+
+    /// Sets the local playback gain for `demux_id`'s incoming audio
+    /// track. `volume` is a linear gain in `0..=200` (100 = unity); out
+    /// of range values are rejected rather than silently clamped so
+    /// callers notice a unit mismatch immediately.
+    pub fn set_participant_volume(&mut self, demux_id: DemuxId, volume: i32) -> Result<()> {
+        if !(0..=200).contains(&volume) {
+            return Err(anyhow!(
+                "participant volume {} out of range 0..=200",
+                volume
+            ));
+        }
+        self.participant_audio.entry(demux_id).or_default().volume = volume as u8;
+        Ok(())
+    }
+
+    /// Returns `(enabled, volume)` for `demux_id`'s incoming audio
+    /// track, used by the render pipeline to apply gain/mute while
+    /// mixing down the remote streams for local playback.
+    pub fn participant_audio_state(&self, demux_id: DemuxId) -> (bool, u8) {
+        let state = self.participant_audio.get(&demux_id).copied().unwrap_or_default();
+        (state.enabled, state.volume)
+    }
+
+    /// Stores (or clears, if `None`) this client's `stats_interval`. No
+    /// statistics are sampled or delivered for a group-call client.
+    pub fn set_stats_interval(&mut self, interval: Option<Duration>) {
+        self.stats_interval = interval;
+    }
+
+    /// Records `hkdf_extra_info` (passed to `create_group_call_client`)
+    /// as the salt used when deriving the media-keys fingerprint.
+    pub fn set_hkdf_extra_info(&mut self, hkdf_extra_info: Vec<u8>) {
+        self.hkdf_extra_info = hkdf_extra_info;
+    }
+
+    /// Records the per-member sender key currently in use for
+    /// `demux_id`, as received over the existing opaque call-message
+    /// channel (`ringrtcReceiveSenderKey`) whenever a member distributes a
+    /// new key -- including our own, once `resend_media_keys` pushes it
+    /// back out to the rest of the call. `cipher_suite` is the suite that
+    /// member actually announced alongside the key, not our own outgoing
+    /// suite -- different members may legitimately pick different suites
+    /// for hardware reasons. If this demux id already has a key on file,
+    /// the new `cipher_suite` is checked against the one already recorded
+    /// for them before being accepted, so a re-key that silently swaps in
+    /// a different suite than this member was using is caught as a
+    /// mismatch rather than overwritten without comment.
+    pub fn set_sender_key(
+        &mut self,
+        demux_id: DemuxId,
+        key: Vec<u8>,
+        cipher_suite: CipherSuite,
+    ) -> Result<()> {
+        if self.sender_key_cipher_suites.contains_key(&demux_id) {
+            self.check_cipher_suite(demux_id, cipher_suite)?;
+        }
+        self.sender_keys.insert(demux_id, key);
+        self.sender_key_cipher_suites.insert(demux_id, cipher_suite);
+        Ok(())
+    }
+
+    /// Negotiates the AEAD cipher suite used for this client's own
+    /// outgoing frame encryption; distributed to other members as
+    /// metadata alongside the next sender key they receive from us.
+    pub fn set_cipher_suite(&mut self, cipher_suite: CipherSuite) {
+        self.cipher_suite = cipher_suite;
+    }
+
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// Confirms `received` -- the cipher suite `demux_id` just announced,
+    /// whether on an incoming frame or a re-keying sender-key message --
+    /// matches what we already expect from them. Called by
+    /// [`Client::set_sender_key`] whenever a member we already have a key
+    /// on file for distributes a new one, so a suite swapped in on rekey
+    /// (e.g. by a tampering SFU) surfaces as a [`CipherSuiteMismatch`]
+    /// instead of silently overwriting the old suite.
+    pub fn check_cipher_suite(&self, demux_id: DemuxId, received: CipherSuite) -> Result<()> {
+        let expected = self
+            .sender_key_cipher_suites
+            .get(&demux_id)
+            .copied()
+            .unwrap_or(self.cipher_suite);
+        if expected != received {
+            return Err(CipherSuiteMismatch {
+                demux_id,
+                expected,
+                received,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Computes a stable fingerprint over the sorted set of sender keys
+    /// currently in use, salted with `hkdf_extra_info`, so members can
+    /// render and cross-check a short safety string out of band. A
+    /// malicious SFU that substitutes its own sender key for a member's
+    /// changes the fingerprint, revealing the tamper.
+    pub fn media_keys_fingerprint(&self) -> [u8; 32] {
+        let mut entries: Vec<(&DemuxId, &Vec<u8>)> = self.sender_keys.iter().collect();
+        entries.sort_by_key(|(demux_id, _)| **demux_id);
+
+        let mut input = Vec::new();
+        for (demux_id, key) in entries {
+            input.extend_from_slice(&demux_id.to_be_bytes());
+            input.extend_from_slice(key);
+        }
+
+        let hk = Hkdf::<Sha256>::new(Some(&self.hkdf_extra_info), &input);
+        let mut fingerprint = [0u8; 32];
+        hk.expand(b"RingRTC-GroupCall-MediaKeysFingerprint", &mut fingerprint)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        fingerprint
+    }
+
+    /// Records that `other_user_id` has asked to join a restricted call
+    /// link and is waiting in the lobby. Called when the SFU's join-request
+    /// notification for this client arrives.
+    pub fn request_join(&mut self, other_user_id: Vec<u8>) {
+        self.pending_users
+            .insert(other_user_id, PendingApprovalState::AwaitingApproval);
+    }
+
+    /// Marks `other_user_id` approved. Returns an error if they aren't
+    /// currently awaiting approval (e.g. a stale or duplicate request).
+    pub fn approve_user(&mut self, other_user_id: &[u8]) -> Result<()> {
+        self.transition_pending_user(other_user_id, PendingApprovalState::Approved)
+    }
+
+    /// Marks `other_user_id` denied. Returns an error if they aren't
+    /// currently awaiting approval.
+    pub fn deny_user(&mut self, other_user_id: &[u8]) -> Result<()> {
+        self.transition_pending_user(other_user_id, PendingApprovalState::Denied)
+    }
+
+    fn transition_pending_user(
+        &mut self,
+        other_user_id: &[u8],
+        new_state: PendingApprovalState,
+    ) -> Result<()> {
+        let state = self
+            .pending_users
+            .get_mut(other_user_id)
+            .ok_or_else(|| anyhow!("user is not awaiting approval"))?;
+        if *state != PendingApprovalState::AwaitingApproval {
+            return Err(anyhow!("user has already been {:?}", state));
+        }
+        *state = new_state;
+        Ok(())
+    }
+
+    /// The user IDs still waiting in the lobby, for rendering an "awaiting
+    /// approval" list in the UI.
+    pub fn pending_users(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.pending_users
+            .iter()
+            .filter(|(_, state)| **state == PendingApprovalState::AwaitingApproval)
+            .map(|(user_id, _)| user_id)
+    }
+
+    /// Stores the admin passkey presented when this client joined a
+    /// restricted call link, so later admission-control actions
+    /// (approve/deny/remove/block) can be authenticated to the SFU without
+    /// having to thread the passkey through every JNI call.
+    pub fn set_admin_passkey(&mut self, admin_passkey: Vec<u8>) {
+        self.admin_passkey = Some(admin_passkey);
+    }
+
+    pub fn admin_passkey(&self) -> Result<&[u8]> {
+        self.admin_passkey
+            .as_deref()
+            .ok_or_else(|| anyhow!("not an admin of this call link"))
+    }
+
+    /// Marks `demux_id` blocked, so a future rejoin attempt from that
+    /// client is refused locally even before the SFU's own block takes
+    /// effect.
+    pub fn block_demux_id(&mut self, demux_id: DemuxId) {
+        self.blocked_demux_ids.insert(demux_id);
+    }
+
+    pub fn is_demux_id_blocked(&self, demux_id: DemuxId) -> bool {
+        self.blocked_demux_ids.contains(&demux_id)
+    }
+
+    /// Reserves a future group call starting at `start_time_ms`, returning
+    /// the `RingId` the app should hand out to invitees (e.g. as a
+    /// calendar-style "join" link) ahead of the call actually starting.
+    pub fn schedule_call(&mut self, start_time_ms: u64, title: Vec<u8>) -> RingId {
+        let nonce = self.next_schedule_nonce;
+        self.next_schedule_nonce += 1;
+        let ring_id = RingId::from_schedule_nonce(nonce);
+        self.scheduled_calls.insert(
+            ring_id,
+            ScheduledCall {
+                start_time_ms,
+                title,
+                window_opened: false,
+            },
+        );
+        ring_id
+    }
+
+    /// Cancels a previously scheduled call. Returns the cancelled
+    /// reservation so the caller can propagate the cancellation to peers
+    /// who were already told about it.
+    pub fn cancel_scheduled_call(&mut self, ring_id: RingId) -> Result<ScheduledCall> {
+        self.scheduled_calls
+            .remove(&ring_id)
+            .ok_or_else(|| anyhow!("no scheduled call with ring id {:?}", ring_id))
+    }
+
+    /// If `ring_id`'s scheduled call's window has opened (`now_ms` has
+    /// reached its `start_time_ms`) and this is the first time we've
+    /// noticed, marks it opened and returns `true` so the caller fires the
+    /// one-shot "window opens" event. Returns `false` on every later call.
+    pub fn mark_scheduled_call_window_opened(&mut self, ring_id: RingId, now_ms: u64) -> bool {
+        match self.scheduled_calls.get_mut(&ring_id) {
+            Some(scheduled) if !scheduled.window_opened && now_ms >= scheduled.start_time_ms => {
+                scheduled.window_opened = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records this client's own demux id, assigned once it joins the SFU.
+    /// Set by the existing join path; `recording_state` needs it to say
+    /// *whose* recording this is.
+    pub fn set_local_demux_id(&mut self, demux_id: DemuxId) {
+        self.local_demux_id = Some(demux_id);
+    }
+
+    /// Starts or stops this client's own recording of the call, returning
+    /// the resulting call-wide `RecordingState` so it can be broadcast to
+    /// the other members and delivered to the app.
+    pub fn set_recording_state(&mut self, recording: bool) -> Result<RecordingState> {
+        let demux_id = self
+            .local_demux_id
+            .ok_or_else(|| anyhow!("local demux id is not yet known"))?;
+        self.recording_state = RecordingState {
+            recording,
+            recorder_demux_id: recording.then_some(demux_id),
+        };
+        Ok(self.recording_state)
+    }
+
+    /// The call's current recording state, for a late joiner who needs to
+    /// be told a recording is already in progress.
+    pub fn recording_state(&self) -> RecordingState {
+        self.recording_state
+    }
+
+    /// Records the media-type hint carried on the ring this client just
+    /// sent, so a subsequent join on this `RingId` defaults to the same
+    /// audio/video mix the ring asked for instead of always assuming both.
+    pub fn set_last_ring_media_types(&mut self, media_types: MediaTypes) {
+        self.last_ring_media_types = media_types;
+    }
+
+    /// The media types the join flow should default to, based on the most
+    /// recent ring this client sent (or "audio and video" if it never
+    /// rang, which preserves the pre-hint behavior).
+    pub fn default_join_media_types(&self) -> MediaTypes {
+        self.last_ring_media_types
+    }
+
+    /// Folds a fresh instantaneous audio level for `demux_id` into its
+    /// smoothed speaker level and re-evaluates its speaking/not-speaking
+    /// state. Fed by `ringrtcSetAudioLevels`, which the app calls once per
+    /// `audio_levels_interval` tick with the same per-participant levels
+    /// it already received and is about to render.
+    pub fn update_speaker_level(&mut self, demux_id: DemuxId, instant_level: f32) {
+        let speaker = self.speaker_levels.entry(demux_id).or_default();
+        speaker.level = instant_level.max(SPEAKER_LEVEL_ALPHA * speaker.level);
+        speaker.speaking = if speaker.speaking {
+            speaker.level > SPEAKER_LEAVE_THRESHOLD
+        } else {
+            speaker.level > SPEAKER_ENTER_THRESHOLD
+        };
+    }
+
+    /// The demux ids currently considered to be speaking, ranked loudest
+    /// first and capped at `top_n`.
+    pub fn top_speakers(&self, top_n: usize) -> Vec<DemuxId> {
+        let mut speaking: Vec<(DemuxId, f32)> = self
+            .speaker_levels
+            .iter()
+            .filter(|(_, speaker)| speaker.speaking)
+            .map(|(demux_id, speaker)| (*demux_id, speaker.level))
+            .collect();
+        speaking.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        speaking.truncate(top_n);
+        speaking.into_iter().map(|(demux_id, _)| demux_id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_types_round_trips_through_i32() {
+        for media_types in [
+            MediaTypes { audio: true, video: true },
+            MediaTypes { audio: true, video: false },
+            MediaTypes { audio: false, video: true },
+        ] {
+            assert_eq!(MediaTypes::from_i32(media_types.as_i32()), media_types);
+        }
+        // No bits set encodes as 0, which decodes as the legacy audio+video default.
+        assert_eq!(
+            MediaTypes::from_i32(MediaTypes { audio: false, video: false }.as_i32()),
+            MediaTypes::default()
+        );
+    }
+
+    #[test]
+    fn media_types_zero_means_audio_and_video_for_legacy_senders() {
+        assert_eq!(MediaTypes::from_i32(0), MediaTypes { audio: true, video: true });
+    }
+
+    #[test]
+    fn cipher_suite_round_trips_through_i32() {
+        for suite in [CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305] {
+            assert_eq!(CipherSuite::from_i32(suite.as_i32()).unwrap(), suite);
+        }
+    }
+
+    #[test]
+    fn cipher_suite_from_i32_rejects_unknown_id() {
+        assert!(CipherSuite::from_i32(42).is_err());
+    }
+
+    #[test]
+    fn set_sender_key_accepts_matching_suite_on_rekey() {
+        let mut client = Client::default();
+        client.set_sender_key(7, vec![1, 2, 3], CipherSuite::ChaCha20Poly1305).unwrap();
+        assert!(client
+            .set_sender_key(7, vec![4, 5, 6], CipherSuite::ChaCha20Poly1305)
+            .is_ok());
+    }
+
+    #[test]
+    fn set_sender_key_rejects_suite_swapped_in_on_rekey() {
+        let mut client = Client::default();
+        client.set_sender_key(7, vec![1, 2, 3], CipherSuite::Aes256Gcm).unwrap();
+        assert!(client
+            .set_sender_key(7, vec![4, 5, 6], CipherSuite::ChaCha20Poly1305)
+            .is_err());
+    }
+
+    #[test]
+    fn transition_pending_user_rejects_non_awaiting_state() {
+        let mut client = Client::default();
+        let user_id = b"user".to_vec();
+        client.request_join(user_id.clone());
+        client.approve_user(&user_id).unwrap();
+        assert!(client.deny_user(&user_id).is_err());
+    }
+
+    #[test]
+    fn transition_pending_user_rejects_unknown_user() {
+        let mut client = Client::default();
+        assert!(client.approve_user(b"never asked").is_err());
+    }
+
+    #[test]
+    fn speaker_level_rises_immediately_and_decays_gradually() {
+        let mut client = Client::default();
+        client.update_speaker_level(1, 0.9);
+        assert_eq!(client.top_speakers(10), vec![1]);
+
+        client.update_speaker_level(1, 0.0);
+        // EMA decay (0.9 * 0.9 = 0.81) keeps it above the leave threshold
+        // right after a single silent tick.
+        assert_eq!(client.top_speakers(10), vec![1]);
+    }
+
+    #[test]
+    fn speaker_hysteresis_requires_clearing_the_enter_threshold_to_start_speaking() {
+        let mut client = Client::default();
+        client.update_speaker_level(1, 0.1);
+        assert!(client.top_speakers(10).is_empty());
+    }
+
+    #[test]
+    fn top_speakers_ranks_loudest_first_and_caps_at_top_n() {
+        let mut client = Client::default();
+        client.update_speaker_level(1, 0.3);
+        client.update_speaker_level(2, 0.9);
+        client.update_speaker_level(3, 0.5);
+        assert_eq!(client.top_speakers(2), vec![2, 3]);
+    }
+}